@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(has_github_token)");
     if option_env!("GITHUB_TOKEN").is_some() {
         println!("cargo:rustc-cfg=has_github_token");
     }
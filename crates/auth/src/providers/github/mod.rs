@@ -49,6 +49,7 @@ pub struct GitHubUser {
     pub id: u64,
 }
 
+#[allow(clippy::result_large_err)]
 pub async fn validate(session: &Session) -> Result<GitHubUser, ValidateError> {
     #[derive(Deserialize)]
     struct Error {
@@ -13,6 +13,7 @@ use oauth2::{AuthorizationCode, CsrfToken, Scope, TokenResponse};
 use rsa::RsaPrivateKey;
 use serde::Deserialize;
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct AuthRequest {
     pub code: String,
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::engine::Backend;
+
+/// The lifecycle action an [AuditEvent] records.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Spawn,
+    Kill,
+}
+
+/// The result of the audited action.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "lowercase", tag = "status", content = "reason")]
+pub enum Outcome {
+    Success,
+    Failure(String),
+}
+
+/// What was run: the engine and backend the workload was spawned with, and its labels.
+#[derive(Clone, Debug, Serialize)]
+pub struct Workload {
+    pub engine: String,
+    pub backend: Option<Backend>,
+    pub labels: HashMap<String, String>,
+}
+
+/// A single audit record describing who did what to which job.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEvent {
+    pub time: u64,
+    pub action: Action,
+    pub id: Uuid,
+    pub identity: Option<String>,
+    pub workload: Workload,
+    pub outcome: Outcome,
+}
+
+impl AuditEvent {
+    /// Constructs a new [AuditEvent] timestamped with the current time.
+    /// The identity is the requester as established by the HTTP auth layer, if any.
+    pub fn new(
+        action: Action,
+        id: Uuid,
+        identity: Option<String>,
+        workload: Workload,
+        outcome: Outcome,
+    ) -> Self {
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            time,
+            action,
+            id,
+            identity,
+            workload,
+            outcome,
+        }
+    }
+}
+
+/// A sink for audit events.
+///
+/// Unlike tracing, an audit writer must not drop events: implementations
+/// should only return once the event has been persisted.
+pub trait AuditWriter: Send + Sync {
+    fn write(&self, event: &AuditEvent) -> io::Result<()>;
+}
+
+/// Writes audit events as JSON lines to a file, syncing after every event.
+pub struct FileWriter(Mutex<File>);
+
+impl FileWriter {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self(Mutex::new(file)))
+    }
+}
+
+impl AuditWriter for FileWriter {
+    fn write(&self, event: &AuditEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let mut file = self.0.lock().unwrap();
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+}
+
+/// Writes audit events as JSON lines to stderr.
+pub struct StderrWriter;
+
+impl AuditWriter for StderrWriter {
+    fn write(&self, event: &AuditEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let mut stderr = io::stderr().lock();
+        stderr.write_all(&line)?;
+        stderr.flush()
+    }
+}
+
+static WRITER: OnceCell<Box<dyn AuditWriter>> = OnceCell::new();
+
+/// Installs the audit writer. Returns the writer back if one is already installed.
+pub fn set_writer(writer: Box<dyn AuditWriter>) -> Result<(), Box<dyn AuditWriter>> {
+    WRITER.set(writer)
+}
+
+/// Records an audit event using the installed writer (stderr by default).
+///
+/// The writer blocks until the event is persisted, so it runs on the blocking thread pool.
+/// Failures are logged and returned, so that callers can refuse to proceed unaudited.
+pub async fn record(event: AuditEvent) -> io::Result<()> {
+    let writer = WRITER.get_or_init(|| Box::new(StderrWriter));
+    let id = event.id;
+    let result = tokio::task::spawn_blocking(move || writer.write(&event))
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)));
+    if let Err(e) = &result {
+        tracing::error!("failed to write audit event for {}: {}", id, e);
+    }
+    result
+}
@@ -0,0 +1,138 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stops spawns after repeated setup failures, so that a host in a bad state
+/// fails fast instead of thrashing.
+pub struct Breaker {
+    threshold: usize,
+    window: Duration,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    failures: usize,
+    first: Option<Instant>,
+    tripped: Option<Instant>,
+}
+
+impl Breaker {
+    /// Constructs a [Breaker] tripping after `threshold` consecutive failures within `window`,
+    /// which then rejects spawns for `cooldown`.
+    pub const fn new(threshold: usize, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            cooldown,
+            state: Mutex::new(State {
+                failures: 0,
+                first: None,
+                tripped: None,
+            }),
+        }
+    }
+
+    /// Whether a spawn may be attempted.
+    ///
+    /// Once the cooldown has passed, spawns are let through again, but a single
+    /// further failure trips the breaker anew.
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.tripped {
+            Some(tripped) if tripped.elapsed() < self.cooldown => false,
+            Some(..) => {
+                state.tripped = None;
+                state.failures = self.threshold.saturating_sub(1);
+                state.first = Some(Instant::now());
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn success(&self) {
+        *self.state.lock().unwrap() = State::default();
+    }
+
+    pub fn failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        match state.first {
+            Some(first) if first.elapsed() < self.window => state.failures += 1,
+            _ => {
+                state.failures = 1;
+                state.first = Some(Instant::now());
+            }
+        }
+
+        if state.failures >= self.threshold && state.tripped.is_none() {
+            tracing::error!(
+                "{} consecutive spawn failures, rejecting spawns for {:?}",
+                state.failures,
+                self.cooldown
+            );
+            state.tripped = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Breaker;
+
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    const SHORT: Duration = Duration::from_millis(20);
+    const LONG: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn trips_after_threshold_failures() {
+        let breaker = Breaker::new(3, LONG, LONG);
+        breaker.failure();
+        breaker.failure();
+        assert!(breaker.allow());
+        breaker.failure();
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn does_not_trip_after_window() {
+        let breaker = Breaker::new(2, SHORT, LONG);
+        breaker.failure();
+        sleep(2 * SHORT);
+        breaker.failure();
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn rejects_during_cooldown() {
+        let breaker = Breaker::new(1, LONG, LONG);
+        breaker.failure();
+        assert!(!breaker.allow());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn retrips_after_single_failure_past_cooldown() {
+        let breaker = Breaker::new(3, LONG, SHORT);
+        breaker.failure();
+        breaker.failure();
+        breaker.failure();
+        assert!(!breaker.allow());
+
+        sleep(2 * SHORT);
+        assert!(breaker.allow());
+        breaker.failure();
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn success_resets() {
+        let breaker = Breaker::new(2, LONG, LONG);
+        breaker.failure();
+        breaker.success();
+        breaker.failure();
+        assert!(breaker.allow());
+    }
+}
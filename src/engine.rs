@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// The range of engine versions whose `run` command line [DEFAULT_ARGS] is known to match.
+const SUPPORTED: Range<(u64, u64, u64)> = (0, 5, 0)..(0, 7, 0);
+
+/// The argument template of engines configured without one, i.e. the `enarx run` command line.
+const DEFAULT_ARGS: &[&str] = &[
+    "run",
+    "--backend={backend}",
+    "--wasmcfgfile",
+    "{config}",
+    "{wasm}",
+];
+
+/// An engine binary and the template of the arguments it is run with.
+///
+/// The template may contain the placeholders `{config}`, `{wasm}` and `{backend}`,
+/// which are replaced with the paths of the workload files and the requested backend.
+/// Arguments containing `{backend}` are left out if the workload does not request a backend.
+#[derive(Clone, Debug)]
+pub struct Engine {
+    binary: PathBuf,
+    args: Vec<String>,
+}
+
+impl Engine {
+    pub fn binary(&self) -> &Path {
+        &self.binary
+    }
+
+    /// Expands the argument template for a workload.
+    pub fn args(&self, config: &Path, wasm: &Path, backend: Option<Backend>) -> Vec<OsString> {
+        self.args
+            .iter()
+            .filter(|arg| backend.is_some() || !arg.contains("{backend}"))
+            .map(|arg| match arg.as_str() {
+                "{config}" => config.into(),
+                "{wasm}" => wasm.into(),
+                arg => arg
+                    .replace("{config}", &config.to_string_lossy())
+                    .replace("{wasm}", &wasm.to_string_lossy())
+                    .replace("{backend}", backend.map_or("", |b| b.as_str()))
+                    .into(),
+            })
+            .collect()
+    }
+}
+
+impl From<PathBuf> for Engine {
+    /// Constructs an [Engine] run with the `enarx run` command line.
+    fn from(binary: PathBuf) -> Self {
+        Self {
+            binary,
+            args: DEFAULT_ARGS.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+}
+
+/// The engines workloads may be run with, keyed by name.
+///
+/// The versions of all engines are checked against the range supported by the
+/// default argument template, whether or not they are configured with one.
+#[derive(Clone, Debug)]
+pub struct Engines {
+    default: String,
+    engines: HashMap<String, Engine>,
+}
+
+impl Engines {
+    /// The name of the engine used when a workload does not request one.
+    pub fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    /// Iterates over the names of all engines and the engines themselves.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Engine)> {
+        self.engines
+            .iter()
+            .map(|(name, engine)| (name.as_str(), engine))
+    }
+
+    /// Looks up the engine with the given name.
+    pub fn get(&self, name: &str) -> Option<&Engine> {
+        self.engines.get(name)
+    }
+}
+
+impl Default for Engines {
+    fn default() -> Self {
+        Self {
+            default: "enarx".into(),
+            engines: HashMap::from([("enarx".into(), PathBuf::from("enarx").into())]),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseEnginesError {
+    Entry(String),
+    Duplicate(String),
+    Template(String),
+}
+
+impl fmt::Display for ParseEnginesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseEnginesError::Entry(engine) => write!(
+                f,
+                "invalid engine `{}`, expected a comma-separated list of `<name>=<path> [<arg>...]`",
+                engine
+            ),
+            ParseEnginesError::Duplicate(name) => write!(f, "engine `{}` is listed twice", name),
+            ParseEnginesError::Template(name) => write!(
+                f,
+                "arguments of engine `{}` must contain `{{config}}` and `{{wasm}}`",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseEnginesError {}
+
+impl FromStr for Engines {
+    type Err = ParseEnginesError;
+
+    /// Parses a comma-separated list of `<name>=<path> [<arg>...]` entries, where the
+    /// whitespace-separated arguments are the template the engine is run with.
+    /// Engines without arguments are run with the `enarx run` command line.
+    /// The first engine listed is the default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut default = None;
+        let mut engines = HashMap::new();
+        for entry in s.split(',') {
+            let invalid = || ParseEnginesError::Entry(entry.into());
+            let (name, command) = entry.split_once('=').ok_or_else(invalid)?;
+            let name = name.trim();
+            let mut command = command.split_whitespace();
+            let binary = command.next().ok_or_else(invalid)?;
+            if name.is_empty() {
+                return Err(invalid());
+            }
+
+            let mut engine = Engine::from(PathBuf::from(binary));
+            let args: Vec<_> = command.map(String::from).collect();
+            if !args.is_empty() {
+                let mentions = |placeholder| args.iter().any(|arg| arg.contains(placeholder));
+                if !mentions("{config}") || !mentions("{wasm}") {
+                    return Err(ParseEnginesError::Template(name.into()));
+                }
+                engine.args = args;
+            }
+
+            if engines.insert(name.to_string(), engine).is_some() {
+                return Err(ParseEnginesError::Duplicate(name.into()));
+            }
+            default.get_or_insert_with(|| name.to_string());
+        }
+
+        Ok(Self {
+            default: default.ok_or_else(|| ParseEnginesError::Entry(s.into()))?,
+            engines,
+        })
+    }
+}
+
+/// The version an engine binary reports.
+#[derive(Clone, Debug, Serialize)]
+pub struct Version {
+    pub version: String,
+    pub supported: bool,
+}
+
+impl Version {
+    /// Runs `<binary> --version` and checks the reported version against the supported range.
+    /// A binary still running after `deadline` is killed and detection fails.
+    pub async fn detect(binary: &Path, deadline: Duration) -> io::Result<Self> {
+        let output = Command::new(binary)
+            .arg("--version")
+            .kill_on_drop(true)
+            .output();
+        let output = timeout(deadline, output).await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "`{} --version` did not exit within {:?}",
+                    binary.display(),
+                    deadline
+                ),
+            )
+        })??;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "`{} --version` exited with {}",
+                binary.display(),
+                output.status
+            )));
+        }
+
+        // The output has the form `enarx 0.6.1`.
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let version = stdout
+            .split_whitespace()
+            .last()
+            .ok_or_else(|| io::Error::other("empty version output"))?;
+        let supported = supported(version);
+
+        Ok(Self {
+            version: version.into(),
+            supported,
+        })
+    }
+}
+
+/// Whether the command line of an engine reporting `version` is known to match [DEFAULT_ARGS].
+fn supported(version: &str) -> bool {
+    parse(version).is_some_and(|v| SUPPORTED.contains(&v))
+}
+
+/// Parses a `<major>.<minor>.<patch>` version, ignoring pre-release and build suffixes.
+fn parse(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(str::parse);
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Some((major, minor, patch)),
+        _ => None,
+    }
+}
+
+/// An Enarx execution backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Sgx,
+    Sev,
+    Kvm,
+    Nil,
+}
+
+impl Backend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::Sgx => "sgx",
+            Backend::Sev => "sev",
+            Backend::Kvm => "kvm",
+            Backend::Nil => "nil",
+        }
+    }
+
+    /// The device the backend requires the host to provide, if any.
+    pub fn device(&self) -> Option<&'static Path> {
+        match self {
+            Backend::Sgx => Some(Path::new("/dev/sgx_enclave")),
+            Backend::Sev => Some(Path::new("/dev/sev")),
+            Backend::Kvm => Some(Path::new("/dev/kvm")),
+            Backend::Nil => None,
+        }
+    }
+
+    /// Whether the host provides the device required by the backend.
+    pub fn is_available(&self) -> bool {
+        self.device().is_none_or(Path::exists)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseBackendError(String);
+
+impl fmt::Display for ParseBackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid backend `{}`, expected `sgx`, `sev`, `kvm` or `nil`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseBackendError {}
+
+impl FromStr for Backend {
+    type Err = ParseBackendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sgx" => Ok(Backend::Sgx),
+            "sev" => Ok(Backend::Sev),
+            "kvm" => Ok(Backend::Kvm),
+            "nil" => Ok(Backend::Nil),
+            _ => Err(ParseBackendError(s.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, supported, Backend, Engines, ParseEnginesError};
+
+    use std::ffi::OsString;
+    use std::path::Path;
+
+    fn args(engines: &Engines, name: &str, backend: Option<Backend>) -> Vec<OsString> {
+        engines
+            .get(name)
+            .unwrap()
+            .args(Path::new("/tmp/conf"), Path::new("/tmp/wasm"), backend)
+    }
+
+    #[test]
+    fn engines_first_is_default() {
+        let engines: Engines = "stable=/usr/bin/enarx,nightly=/opt/enarx".parse().unwrap();
+        assert_eq!(engines.default_name(), "stable");
+        assert_eq!(
+            engines.get("nightly").unwrap().binary(),
+            Path::new("/opt/enarx")
+        );
+        assert!(engines.get("enarx").is_none());
+    }
+
+    #[test]
+    fn engines_rejects_malformed_lists() {
+        for list in [
+            "",
+            "enarx",
+            "=enarx",
+            "enarx=",
+            "enarx=enarx,",
+            ",enarx=enarx",
+        ] {
+            assert!(
+                matches!(list.parse::<Engines>(), Err(ParseEnginesError::Entry(..))),
+                "`{}` should be rejected",
+                list
+            );
+        }
+    }
+
+    #[test]
+    fn engines_rejects_duplicates() {
+        assert!(matches!(
+            "enarx=/a,enarx=/b".parse::<Engines>(),
+            Err(ParseEnginesError::Duplicate(name)) if name == "enarx"
+        ));
+    }
+
+    #[test]
+    fn engines_default_template() {
+        let engines: Engines = "enarx=/usr/bin/enarx".parse().unwrap();
+        assert_eq!(
+            args(&engines, "enarx", None),
+            ["run", "--wasmcfgfile", "/tmp/conf", "/tmp/wasm"]
+        );
+        assert_eq!(
+            args(&engines, "enarx", Some(Backend::Kvm)),
+            [
+                "run",
+                "--backend=kvm",
+                "--wasmcfgfile",
+                "/tmp/conf",
+                "/tmp/wasm"
+            ]
+        );
+    }
+
+    #[test]
+    fn engines_custom_template() {
+        let engines: Engines =
+            "podman=/usr/bin/podman run -v {config}:/Enarx.toml -v {wasm}:/main.wasm --env=BACKEND={backend} enarx"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            args(&engines, "podman", Some(Backend::Sev)),
+            [
+                "run",
+                "-v",
+                "/tmp/conf:/Enarx.toml",
+                "-v",
+                "/tmp/wasm:/main.wasm",
+                "--env=BACKEND=sev",
+                "enarx"
+            ]
+        );
+        assert_eq!(
+            args(&engines, "podman", None),
+            [
+                "run",
+                "-v",
+                "/tmp/conf:/Enarx.toml",
+                "-v",
+                "/tmp/wasm:/main.wasm",
+                "enarx"
+            ]
+        );
+    }
+
+    #[test]
+    fn engines_rejects_templates_without_workload() {
+        assert!(matches!(
+            "enarx=/usr/bin/enarx run {wasm}".parse::<Engines>(),
+            Err(ParseEnginesError::Template(name)) if name == "enarx"
+        ));
+    }
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(parse("0.6.1"), Some((0, 6, 1)));
+        assert_eq!(parse("0.6.1-rc1"), Some((0, 6, 1)));
+        assert_eq!(parse("0.6.1+build.5"), Some((0, 6, 1)));
+        assert_eq!(parse("0.6.1-rc1+build"), Some((0, 6, 1)));
+        assert_eq!(parse("0.6"), None);
+        assert_eq!(parse("0.6.1.2"), None);
+        assert_eq!(parse("v0.6.1"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn supported_range_bounds() {
+        assert!(!supported("0.4.99"));
+        assert!(supported("0.5.0"));
+        assert!(supported("0.5.0-rc1"));
+        assert!(supported("0.6.99"));
+        assert!(!supported("0.7.0"));
+        assert!(!supported("1.0.0"));
+        assert!(!supported("unknown"));
+    }
+}
@@ -0,0 +1,40 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// The number of events buffered per subscriber before it starts missing events.
+const CAPACITY: usize = 256;
+
+/// A job lifecycle change.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Spawned,
+    Paused,
+    Resumed,
+    /// The engine exited on its own, before the job was killed.
+    Exited,
+    Killed,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct JobEvent {
+    pub id: Uuid,
+    pub kind: Kind,
+}
+
+static EVENTS: Lazy<broadcast::Sender<JobEvent>> = Lazy::new(|| broadcast::channel(CAPACITY).0);
+
+/// Publishes an event to all current subscribers.
+pub fn emit(id: Uuid, kind: Kind) {
+    // Sending only fails if there are no subscribers, which is fine.
+    let _ = EVENTS.send(JobEvent { id, kind });
+}
+
+/// Subscribes to events emitted from now on.
+///
+/// Subscribers that fall more than [CAPACITY] events behind skip the oldest ones.
+pub fn subscribe() -> broadcast::Receiver<JobEvent> {
+    EVENTS.subscribe()
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::io;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::Duration;
+
+use tokio::process::Command;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+/// An operator-provided command run around the job lifecycle.
+///
+/// The job id is passed to the command in the `BENEFICE_JOB_ID` environment variable.
+#[derive(Clone, Debug)]
+pub struct Hook(Vec<String>);
+
+impl Hook {
+    /// Runs the hook to completion, failing if it does not exit successfully.
+    /// A hook still running after `deadline` is killed and reported as failed.
+    pub async fn run(&self, id: Uuid, deadline: Duration) -> io::Result<()> {
+        let status = Command::new(&self.0[0])
+            .args(&self.0[1..])
+            .env("BENEFICE_JOB_ID", id.to_string())
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .status();
+        let status = timeout(deadline, status).await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("`{}` did not exit within {:?}", self.0[0], deadline),
+            )
+        })??;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "`{}` exited with {}",
+                self.0[0], status
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseHookError;
+
+impl fmt::Display for ParseHookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hook command must not be empty")
+    }
+}
+
+impl std::error::Error for ParseHookError {}
+
+impl FromStr for Hook {
+    type Err = ParseHookError;
+
+    /// Parses a whitespace-separated argument vector.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args: Vec<_> = s.split_whitespace().map(String::from).collect();
+        if args.is_empty() {
+            return Err(ParseHookError);
+        }
+        Ok(Self(args))
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+
+use tokio::process::Command;
+
+/// Resource controls applied to the engine process only, never to benefice itself.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    nice: Option<i32>,
+    nofile: Option<u64>,
+    cpus: Option<Vec<usize>>,
+}
+
+#[derive(Debug)]
+pub enum LimitsError {
+    Nice(i32),
+    Nofile { requested: u64, max: u64 },
+    CpuList(String),
+    Cpu { requested: usize, count: usize },
+    Io(io::Error),
+}
+
+impl fmt::Display for LimitsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitsError::Nice(n) => write!(f, "nice value {} is outside of -20..=19", n),
+            LimitsError::Nofile { requested, max } => write!(
+                f,
+                "open file limit {} exceeds the hard limit of {}",
+                requested, max
+            ),
+            LimitsError::CpuList(s) => write!(f, "invalid CPU list `{}`", s),
+            LimitsError::Cpu { requested, count } => write!(
+                f,
+                "CPU {} does not exist, the host has {} CPUs",
+                requested, count
+            ),
+            LimitsError::Io(e) => write!(f, "failed to query resource limits: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LimitsError {}
+
+impl Limits {
+    /// Sets the niceness of the engine process.
+    pub fn nice(mut self, nice: i32) -> Result<Self, LimitsError> {
+        if !(-20..=19).contains(&nice) {
+            return Err(LimitsError::Nice(nice));
+        }
+        self.nice = Some(nice);
+        Ok(self)
+    }
+
+    /// Sets the maximum number of open file descriptors of the engine process.
+    /// The limit may not exceed benefice's own hard limit.
+    pub fn nofile(mut self, nofile: u64) -> Result<Self, LimitsError> {
+        let mut rlimit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+
+        // SAFETY: rlimit is a valid, writable rlimit struct.
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlimit) } != 0 {
+            return Err(LimitsError::Io(io::Error::last_os_error()));
+        }
+        if nofile > rlimit.rlim_max {
+            return Err(LimitsError::Nofile {
+                requested: nofile,
+                max: rlimit.rlim_max,
+            });
+        }
+
+        self.nofile = Some(nofile);
+        Ok(self)
+    }
+
+    /// Pins the engine process to the CPUs in `list`, given in the format
+    /// accepted by `taskset -c`, e.g. `0,2-3`.
+    pub fn cpus(mut self, list: &str) -> Result<Self, LimitsError> {
+        // SAFETY: sysconf has no preconditions.
+        let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+        if count < 0 {
+            return Err(LimitsError::Io(io::Error::last_os_error()));
+        }
+
+        self.cpus = Some(parse_cpus(list, count as usize)?);
+        Ok(self)
+    }
+
+    /// Applies the limits to the command in the child, between fork and exec.
+    pub fn apply(&self, cmd: &mut Command) {
+        let limits = self.clone();
+
+        // The CPU set is built here, as allocating is not allowed after fork.
+        let cpus = self.cpus.as_ref().map(|cpus| {
+            // SAFETY: cpu_set_t is plain data, for which all zeroes is the empty set.
+            let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+            for &cpu in cpus {
+                // SAFETY: cpus are validated to be below CPU_SETSIZE.
+                unsafe { libc::CPU_SET(cpu, &mut set) };
+            }
+            set
+        });
+
+        // SAFETY: the closure only performs async-signal-safe system calls.
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(nice) = limits.nice {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if let Some(nofile) = limits.nofile {
+                    let rlimit = libc::rlimit {
+                        rlim_cur: nofile,
+                        rlim_max: nofile,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if let Some(set) = &cpus {
+                    let size = std::mem::size_of::<libc::cpu_set_t>();
+                    if libc::sched_setaffinity(0, size, set) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Parses a CPU list, checking every CPU is below `count` and fits in a `cpu_set_t`.
+/// Returns the CPUs in ascending order, without duplicates.
+fn parse_cpus(list: &str, count: usize) -> Result<Vec<usize>, LimitsError> {
+    let invalid = || LimitsError::CpuList(list.into());
+    let bound = count.min(libc::CPU_SETSIZE as usize);
+
+    let mut cpus = BTreeSet::new();
+    for range in list.split(',') {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end: usize = end.trim().parse().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+        // Checked before expanding the range, which may be arbitrarily large.
+        if end >= bound {
+            return Err(LimitsError::Cpu {
+                requested: end,
+                count,
+            });
+        }
+        cpus.extend(start..=end);
+    }
+    Ok(cpus.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cpus, Limits, LimitsError};
+
+    #[test]
+    fn cpus_parses_lists_and_ranges() {
+        assert_eq!(parse_cpus("0", 4).unwrap(), [0]);
+        assert_eq!(parse_cpus("1-3", 4).unwrap(), [1, 2, 3]);
+        assert_eq!(parse_cpus("3, 0-1", 4).unwrap(), [0, 1, 3]);
+    }
+
+    #[test]
+    fn cpus_deduplicates() {
+        assert_eq!(parse_cpus("0,0-2,1", 4).unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn cpus_rejects_malformed_lists() {
+        for list in ["", "a", "0,", "1-0", "-1", "0-"] {
+            assert!(
+                matches!(parse_cpus(list, 4), Err(LimitsError::CpuList(..))),
+                "`{}` should be rejected",
+                list
+            );
+        }
+    }
+
+    #[test]
+    fn cpus_rejects_missing_cpus() {
+        assert!(matches!(
+            parse_cpus("0,4", 4),
+            Err(LimitsError::Cpu {
+                requested: 4,
+                count: 4
+            })
+        ));
+        assert!(matches!(
+            parse_cpus("2-5", 4),
+            Err(LimitsError::Cpu { requested: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn cpus_rejects_huge_ranges_without_expanding() {
+        let list = format!("0-{}", usize::MAX);
+        assert!(matches!(
+            parse_cpus(&list, 4),
+            Err(LimitsError::Cpu { requested, .. }) if requested == usize::MAX
+        ));
+        assert!(Limits::default().cpus(&list).is_err());
+    }
+}
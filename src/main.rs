@@ -1,40 +1,371 @@
+mod audit;
+mod breaker;
+mod engine;
+mod events;
+mod hook;
+mod limits;
+mod policy;
+mod stdio;
+
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use axum::extract::Path;
+use axum::extract::multipart::Field;
+use axum::extract::{Path, Query};
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use axum::{extract::Multipart, response::Html};
-use axum::{Router, Server};
+use axum::{Json, Router, Server};
 
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
 use tempfile::NamedTempFile;
-use tokio::io::AsyncReadExt;
-use tokio::process::{Child, Command};
-use tokio::sync::{Mutex, RwLock};
-use tokio::time::{sleep, timeout};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStderr, ChildStdout, Command};
+use tokio::sync::{Mutex, RwLock, Semaphore};
+use tokio::time::{sleep, timeout, timeout_at};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
 
+use audit::{Action, AuditEvent, Outcome, Workload};
+use breaker::Breaker;
+use engine::{Backend, Engines, Version};
+use hook::Hook;
+use limits::Limits;
+use policy::Policy;
+use stdio::StdioMode;
+
 const READ_TIMEOUT: Duration = Duration::from_secs(5);
 const VIEW_TIMEOUT: Duration = Duration::from_secs(10);
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+const EXIT_POLL: Duration = Duration::from_secs(1);
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
 const WASM_MAX: usize = 25 * 1024 * 1024; // 25 MiB
 const TOML_MAX: usize = 256 * 1024; // 256 KiB
+const JOBS_MAX: usize = 64;
+const NAME_MAX: usize = 64;
+const LABEL_MAX: usize = 256;
+const LABELS_MAX: usize = 16;
+const SETUPS_MAX: usize = 8;
+const BREAKER_THRESHOLD: usize = 5;
+const BREAKER_WINDOW: Duration = Duration::from_secs(60);
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// A registered job.
+///
+/// What the job runs and when it was created never change, so they live outside
+/// of the lock and can be read while the job is busy. The output of the engine has
+/// locks of its own, so that a pending read never blocks the rest of the job.
+struct Job {
+    created: Instant,
+    workload: Workload,
+    state: Mutex<State>,
+    stdout: Mutex<Option<ChildStdout>>,
+    stderr: Mutex<Option<ChildStderr>>,
+}
+
+impl Job {
+    /// Takes a snapshot of the job suitable for rendering to clients.
+    /// Host-local details, such as the paths of the uploaded files, are never included.
+    async fn describe(&self) -> JobDescription {
+        let mut state = self.state.lock().await;
+        let status = match state.exec.try_wait() {
+            Ok(None) if state.paused => Status::Paused,
+            Ok(None) => Status::Running,
+            Ok(Some(status)) => Status::Exited {
+                code: status.code(),
+            },
+            Err(..) => Status::Unknown,
+        };
+
+        JobDescription {
+            id: state.id,
+            workload: "upload",
+            engine: self.workload.engine.clone(),
+            backend: self.workload.backend,
+            labels: self.workload.labels.clone(),
+            status,
+            age: self.created.elapsed().as_secs(),
+            pid: state.exec.id(),
+        }
+    }
+}
 
-#[allow(dead_code)]
 struct State {
+    id: Uuid,
     exec: Child,
-    wasm: NamedTempFile,
-    toml: NamedTempFile,
+    wasm: Option<NamedTempFile>,
+    toml: Option<NamedTempFile>,
+    paused: bool,
+    killed: bool,
 }
 
-static OUT: Lazy<RwLock<HashMap<Uuid, Arc<Mutex<State>>>>> =
-    Lazy::new(|| RwLock::new(HashMap::new()));
+impl State {
+    /// Kills the engine and removes the uploaded files, reporting the outcome of every step.
+    /// Failures are recorded, but teardown always proceeds to the next step.
+    ///
+    /// If teardown does not complete by `deadline`, the engine is sent `SIGKILL` without
+    /// waiting for it to exit and the steps that did not complete are reported as failed.
+    async fn kill(&mut self, deadline: tokio::time::Instant) -> CleanupReport {
+        let mut report = CleanupReport::default();
+
+        if timeout_at(deadline, self.teardown(&mut report))
+            .await
+            .is_err()
+        {
+            report.expired();
+            if let Ok(None) = self.exec.try_wait() {
+                report.step("exec", self.exec.start_kill());
+            }
+            if self.wasm.is_some() {
+                report.skipped("wasm");
+            }
+            if self.toml.is_some() {
+                report.skipped("toml");
+            }
+        }
+
+        self.killed = true;
+        report
+    }
+
+    async fn teardown(&mut self, report: &mut CleanupReport) {
+        let exec = match self.exec.try_wait() {
+            Ok(Some(..)) => Ok(()),
+            _ => self.exec.kill().await,
+        };
+        report.step("exec", exec);
+        report.step(
+            "wasm",
+            self.wasm.take().map_or(Ok(()), NamedTempFile::close),
+        );
+        report.step(
+            "toml",
+            self.toml.take().map_or(Ok(()), NamedTempFile::close),
+        );
+    }
+
+    /// Suspends the engine with `SIGSTOP`.
+    /// Fails if the engine is already paused.
+    fn pause(&mut self) -> std::io::Result<()> {
+        if self.paused {
+            return Err(std::io::Error::other("engine is already paused"));
+        }
+        self.signal(libc::SIGSTOP)?;
+        self.paused = true;
+        Ok(())
+    }
+
+    /// Resumes a paused engine with `SIGCONT`.
+    /// Fails if the engine is not paused.
+    fn resume(&mut self) -> std::io::Result<()> {
+        if !self.paused {
+            return Err(std::io::Error::other("engine is not paused"));
+        }
+        self.signal(libc::SIGCONT)?;
+        self.paused = false;
+        Ok(())
+    }
+
+    fn signal(&mut self, signal: libc::c_int) -> std::io::Result<()> {
+        let pid = self.exec.id().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "engine has already exited")
+        })?;
+
+        // SAFETY: the pid belongs to our own child, which has not been reaped yet.
+        if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // The engine is killed on drop and the uploaded files are removed on drop,
+        // so reaching this is not a leak, but it does indicate a lifecycle bug.
+        if !self.killed {
+            tracing::warn!("job {} was dropped without being killed", self.id);
+        }
+    }
+}
+
+/// The outcome of a job teardown.
+#[derive(Debug, Default)]
+struct CleanupReport {
+    failures: Vec<(&'static str, String)>,
+}
+
+impl CleanupReport {
+    fn step(&mut self, name: &'static str, result: std::io::Result<()>) {
+        if let Err(e) = result {
+            self.failures.push((name, e.to_string()));
+        }
+    }
+
+    /// Records that teardown did not complete by its deadline.
+    fn expired(&mut self) {
+        self.failures
+            .push(("deadline", "teardown did not complete in time".into()));
+    }
+
+    /// Records a step that was not reached before the deadline.
+    fn skipped(&mut self, name: &'static str) {
+        self.failures
+            .push((name, "skipped after the deadline".into()));
+    }
+
+    fn outcome(&self) -> Outcome {
+        if self.failures.is_empty() {
+            return Outcome::Success;
+        }
+
+        let steps: Vec<_> = self.failures.iter().map(|(step, _)| *step).collect();
+        Outcome::Failure(format!("failed to clean up {}", steps.join(", ")))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase", tag = "state")]
+enum Status {
+    Running,
+    Paused,
+    Exited { code: Option<i32> },
+    Unknown,
+}
+
+#[derive(Serialize)]
+struct JobDescription {
+    id: Uuid,
+    workload: &'static str,
+    engine: String,
+    backend: Option<Backend>,
+    labels: HashMap<String, String>,
+    status: Status,
+    age: u64,
+    pid: Option<u32>,
+}
+
+static BREAKER: Breaker = Breaker::new(BREAKER_THRESHOLD, BREAKER_WINDOW, BREAKER_COOLDOWN);
+
+/// Bounds how many spawns may be setting up at once, so bursts queue instead of
+/// all running hooks and launching engines simultaneously.
+static SETUP: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(*SETUPS_LIMIT));
+
+static SETUPS_LIMIT: Lazy<usize> = Lazy::new(|| {
+    std::env::var("BENEFICE_SETUPS_MAX")
+        .map(|max| {
+            max.parse::<NonZeroUsize>()
+                .expect("invalid BENEFICE_SETUPS_MAX")
+                .get()
+        })
+        .unwrap_or(SETUPS_MAX)
+});
+
+static STDIO: Lazy<StdioMode> = Lazy::new(|| {
+    std::env::var("BENEFICE_STDIO")
+        .map(|mode| mode.parse().expect("invalid BENEFICE_STDIO"))
+        .unwrap_or_default()
+});
+
+static ENGINES: Lazy<Engines> = Lazy::new(|| {
+    std::env::var("BENEFICE_ENGINES")
+        .map(|engines| engines.parse().expect("invalid BENEFICE_ENGINES"))
+        .unwrap_or_default()
+});
+
+static PRE_SPAWN: Lazy<Option<Hook>> = Lazy::new(|| {
+    std::env::var("BENEFICE_PRE_SPAWN")
+        .ok()
+        .map(|hook| hook.parse().expect("invalid BENEFICE_PRE_SPAWN"))
+});
+
+static POST_KILL: Lazy<Option<Hook>> = Lazy::new(|| {
+    std::env::var("BENEFICE_POST_KILL")
+        .ok()
+        .map(|hook| hook.parse().expect("invalid BENEFICE_POST_KILL"))
+});
+
+static LIMITS: Lazy<Limits> = Lazy::new(|| {
+    let mut limits = Limits::default();
+    if let Ok(nice) = std::env::var("BENEFICE_NICE") {
+        let nice = nice.parse().expect("invalid BENEFICE_NICE");
+        limits = limits.nice(nice).expect("invalid BENEFICE_NICE");
+    }
+    if let Ok(nofile) = std::env::var("BENEFICE_NOFILE") {
+        let nofile = nofile.parse().expect("invalid BENEFICE_NOFILE");
+        limits = limits.nofile(nofile).expect("invalid BENEFICE_NOFILE");
+    }
+    if let Ok(cpus) = std::env::var("BENEFICE_CPUS") {
+        limits = limits.cpus(&cpus).expect("invalid BENEFICE_CPUS");
+    }
+    limits
+});
+
+/// The versions of the configured engines, detected at startup.
+static VERSIONS: OnceCell<HashMap<String, Option<Version>>> = OnceCell::new();
+
+static BACKENDS: Lazy<Vec<Backend>> = Lazy::new(|| {
+    [Backend::Sgx, Backend::Sev, Backend::Kvm, Backend::Nil]
+        .into_iter()
+        .filter(Backend::is_available)
+        .collect()
+});
+
+static WASM_LIMIT: Lazy<usize> = Lazy::new(|| {
+    std::env::var("BENEFICE_WASM_MAX")
+        .map(|max| max.parse().expect("invalid BENEFICE_WASM_MAX"))
+        .unwrap_or(WASM_MAX)
+});
+
+static JOBS_LIMIT: Lazy<usize> = Lazy::new(|| {
+    std::env::var("BENEFICE_JOBS_MAX")
+        .map(|max| max.parse().expect("invalid BENEFICE_JOBS_MAX"))
+        .unwrap_or(JOBS_MAX)
+});
+
+static TOML_LIMIT: Lazy<usize> = Lazy::new(|| {
+    std::env::var("BENEFICE_TOML_MAX")
+        .map(|max| max.parse().expect("invalid BENEFICE_TOML_MAX"))
+        .unwrap_or(TOML_MAX)
+});
+
+static POLICY: Lazy<Option<Policy>> = Lazy::new(|| {
+    std::env::var("BENEFICE_ALLOWED_FILE_KINDS")
+        .ok()
+        .map(|kinds| kinds.parse().expect("invalid BENEFICE_ALLOWED_FILE_KINDS"))
+});
+
+static CONFIG_STDIN: Lazy<bool> = Lazy::new(|| {
+    std::env::var("BENEFICE_CONFIG_STDIN")
+        .map(|stdin| stdin.parse().expect("invalid BENEFICE_CONFIG_STDIN"))
+        .unwrap_or_default()
+});
+
+static ALLOW_UNSUPPORTED_ENGINES: Lazy<bool> = Lazy::new(|| {
+    std::env::var("BENEFICE_ALLOW_UNSUPPORTED_ENGINES")
+        .map(|allow| {
+            allow
+                .parse()
+                .expect("invalid BENEFICE_ALLOW_UNSUPPORTED_ENGINES")
+        })
+        .unwrap_or_default()
+});
+
+static OUT: Lazy<RwLock<HashMap<Uuid, Arc<Job>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
 
 #[tokio::main]
 async fn main() {
@@ -46,11 +377,65 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    if let Ok(path) = std::env::var("BENEFICE_AUDIT_LOG") {
+        let writer = audit::FileWriter::open(&path).expect("failed to open audit log");
+        let _ = audit::set_writer(Box::new(writer));
+    }
+
+    Lazy::force(&STDIO);
+    Lazy::force(&ENGINES);
+
+    // The command line of an engine whose version is unknown cannot be trusted
+    // any more than that of an unsupported one.
+    let mut versions = HashMap::new();
+    for (name, engine) in ENGINES.iter() {
+        let version = Version::detect(engine.binary(), VERSION_TIMEOUT).await;
+        let problem = match &version {
+            Ok(version) if version.supported => None,
+            Ok(version) => Some(format!(
+                "engine `{}` has unsupported version {}",
+                name, version.version
+            )),
+            Err(e) => Some(format!(
+                "failed to detect the version of engine `{}`: {}",
+                name, e
+            )),
+        };
+        if let Some(problem) = problem {
+            if !*ALLOW_UNSUPPORTED_ENGINES {
+                panic!("{}", problem);
+            }
+            tracing::warn!("{}", problem);
+        }
+        versions.insert(name.to_string(), version.ok());
+    }
+    VERSIONS.set(versions).unwrap();
+    Lazy::force(&PRE_SPAWN);
+    Lazy::force(&POST_KILL);
+    Lazy::force(&LIMITS);
+    Lazy::force(&WASM_LIMIT);
+    Lazy::force(&TOML_LIMIT);
+    Lazy::force(&JOBS_LIMIT);
+    Lazy::force(&SETUP);
+    Lazy::force(&POLICY);
+    Lazy::force(&CONFIG_STDIN);
+
+    let backends: Vec<_> = BACKENDS.iter().map(Backend::as_str).collect();
+    tracing::info!("available backends: {}", backends.join(", "));
+
     let app = Router::new()
-        .route("/:uuid/", get(uuid_get))
+        .route("/:uuid/", get(uuid_get).delete(uuid_delete))
+        .route("/:uuid/pause", post(uuid_pause_post))
+        .route("/:uuid/resume", post(uuid_resume_post))
         .route("/:uuid/out", post(uuid_out_post))
         .route("/:uuid/err", post(uuid_err_post))
         .route("/", get(root_get).post(root_post))
+        .route("/capacity", get(capacity_get))
+        .route("/engines", get(engines_get))
+        .route("/reclaim", post(reclaim_post))
+        .route("/jobs", get(jobs_get))
+        .route("/jobs/:uuid", get(jobs_uuid_get))
+        .route("/events", get(events_get))
         .layer(TraceLayer::new_for_http());
 
     Server::bind(&"0.0.0.0:3000".parse().unwrap())
@@ -63,9 +448,16 @@ async fn root_get() -> Html<&'static str> {
     Html(include_str!("root_get.html"))
 }
 
-async fn root_post(mut multipart: Multipart) -> Result<impl IntoResponse, StatusCode> {
+async fn root_post(mut multipart: Multipart) -> Result<impl IntoResponse, Rejection> {
+    if !BREAKER.allow() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE.into());
+    }
+
     let mut wasm = None;
     let mut toml = None;
+    let mut engine = None;
+    let mut backend = None;
+    let mut labels = HashMap::new();
 
     while let Some(mut field) = multipart
         .next_field()
@@ -75,11 +467,11 @@ async fn root_post(mut multipart: Multipart) -> Result<impl IntoResponse, Status
         match field.name() {
             Some("wasm") => {
                 if Some("application/wasm") != field.content_type() {
-                    return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                    return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE.into());
                 }
 
                 if wasm.is_some() {
-                    return Err(StatusCode::BAD_REQUEST);
+                    return Err(StatusCode::BAD_REQUEST.into());
                 }
 
                 let mut len = 0;
@@ -88,8 +480,8 @@ async fn root_post(mut multipart: Multipart) -> Result<impl IntoResponse, Status
 
                 while let Some(chunk) = field.chunk().await.map_err(|_| StatusCode::BAD_REQUEST)? {
                     len += chunk.len();
-                    if len > WASM_MAX {
-                        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+                    if len > *WASM_LIMIT {
+                        return Err(StatusCode::PAYLOAD_TOO_LARGE.into());
                     }
 
                     out.write_all(&chunk)
@@ -101,67 +493,540 @@ async fn root_post(mut multipart: Multipart) -> Result<impl IntoResponse, Status
 
             Some("toml") => {
                 if field.content_type().is_some() {
-                    return Err(StatusCode::BAD_REQUEST);
+                    return Err(StatusCode::BAD_REQUEST.into());
                 }
 
                 if toml.is_some() {
-                    return Err(StatusCode::BAD_REQUEST);
+                    return Err(StatusCode::BAD_REQUEST.into());
                 }
 
-                let mut len = 0;
-                let mut out = tempfile::NamedTempFile::new()
-                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
+                let mut out = Vec::new();
                 while let Some(chunk) = field.chunk().await.map_err(|_| StatusCode::BAD_REQUEST)? {
-                    len += chunk.len();
-                    if len > TOML_MAX {
-                        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+                    if out.len() + chunk.len() > *TOML_LIMIT {
+                        return Err(StatusCode::PAYLOAD_TOO_LARGE.into());
                     }
 
-                    out.write_all(&chunk)
-                        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                    out.extend_from_slice(&chunk);
                 }
 
                 toml = Some(out);
             }
 
+            Some("engine") => {
+                if engine.is_some() {
+                    return Err(StatusCode::BAD_REQUEST.into());
+                }
+
+                engine = Some(read_text(field, NAME_MAX).await?);
+            }
+
+            Some("backend") => {
+                if backend.is_some() {
+                    return Err(StatusCode::BAD_REQUEST.into());
+                }
+
+                let name = read_text(field, NAME_MAX).await?;
+                backend = Some(name.parse().map_err(|_| StatusCode::BAD_REQUEST)?);
+            }
+
+            Some("label") => {
+                if labels.len() >= LABELS_MAX {
+                    return Err(StatusCode::BAD_REQUEST.into());
+                }
+
+                let label = read_text(field, LABEL_MAX).await?;
+                let (key, value) = label
+                    .split_once('=')
+                    .filter(|(key, _)| !key.is_empty())
+                    .ok_or(StatusCode::BAD_REQUEST)?;
+                if labels.insert(key.into(), value.into()).is_some() {
+                    return Err(StatusCode::BAD_REQUEST.into());
+                }
+            }
+
             _ => continue,
         }
     }
 
     let wasm = wasm.ok_or(StatusCode::BAD_REQUEST)?;
     let toml = toml.ok_or(StatusCode::BAD_REQUEST)?;
+    if let Some(policy) = POLICY.as_ref() {
+        policy
+            .validate(&toml)
+            .map_err(|e| Rejection(StatusCode::BAD_REQUEST, Some(e.to_string())))?;
+    }
+    let engine = engine.unwrap_or_else(|| ENGINES.default_name().into());
+    let command = ENGINES.get(&engine).ok_or(StatusCode::BAD_REQUEST)?;
+    if let Some(backend) = backend.filter(|b| !BACKENDS.contains(b)) {
+        let mut message = format!("backend `{}` is not available", backend.as_str());
+        if let Some(device) = backend.device() {
+            message += &format!(": {} does not exist on this host", device.display());
+        }
+        tracing::warn!("{}", message);
+        return Err(Rejection(StatusCode::SERVICE_UNAVAILABLE, Some(message)));
+    }
     let uuid = Uuid::new_v4();
-    let exec = Command::new("enarx")
-        .arg("run")
-        .arg("--wasmcfgfile")
-        .arg(toml.path())
-        .arg(wasm.path())
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .kill_on_drop(true)
-        .spawn()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    OUT.write()
+    let workload = Workload {
+        engine,
+        backend,
+        labels,
+    };
+
+    // Either hand the config to the engine on its stdin, or persist it for the
+    // lifetime of the job.
+    let (conf, conf_file) = if *CONFIG_STDIN {
+        (PathBuf::from("/dev/stdin"), None)
+    } else {
+        let mut file = NamedTempFile::new().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        file.write_all(&toml)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (file.path().to_path_buf(), Some(file))
+    };
+
+    let queued = Instant::now();
+    let _setup = SETUP
+        .acquire()
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    tracing::info!("job {} waited {:?} to start setup", uuid, queued.elapsed());
+
+    // Fail early rather than running the pre-spawn hook for a job that cannot start.
+    // The budget is checked again under the write lock before spawning.
+    if OUT.read().await.len() >= *JOBS_LIMIT {
+        return Err(StatusCode::SERVICE_UNAVAILABLE.into());
+    }
+
+    if let Some(hook) = PRE_SPAWN.as_ref() {
+        if let Err(e) = hook.run(uuid, HOOK_TIMEOUT).await {
+            let _ = audit::record(AuditEvent::new(
+                Action::Spawn,
+                uuid,
+                None,
+                workload,
+                Outcome::Failure(format!("pre-spawn hook failed: {}", e)),
+            ))
+            .await;
+            BREAKER.failure();
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+    }
+
+    // From here on, every failure must run the post-kill hook to undo the pre-spawn hook,
+    // including the request being cancelled, until the job is registered.
+    let undo = Undo(Some(uuid));
+    let (stdout, stderr) = match STDIO.stdio().and_then(|out| Ok((out, STDIO.stdio()?))) {
+        Ok(stdio) => stdio,
+        Err(e) => {
+            let _ = audit::record(AuditEvent::new(
+                Action::Spawn,
+                uuid,
+                None,
+                workload,
+                Outcome::Failure(format!("failed to open engine stdio: {}", e)),
+            ))
+            .await;
+            BREAKER.failure();
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+    };
+
+    let mut cmd = Command::new(command.binary());
+    cmd.args(command.args(&conf, wasm.path(), backend))
+        .stdin(if *CONFIG_STDIN {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(stdout)
+        .stderr(stderr)
+        .kill_on_drop(true);
+    LIMITS.apply(&mut cmd);
+
+    // Hold the registry lock across the spawn so the budget check and the
+    // insert are atomic with respect to concurrent uploads.
+    let mut out = OUT.write().await;
+    if out.len() >= *JOBS_LIMIT {
+        drop(out);
+        let _ = audit::record(AuditEvent::new(
+            Action::Spawn,
+            uuid,
+            None,
+            workload,
+            Outcome::Failure("job limit reached".into()),
+        ))
+        .await;
+        return Err(StatusCode::SERVICE_UNAVAILABLE.into());
+    }
+
+    let mut exec = match cmd.spawn() {
+        Ok(exec) => exec,
+        Err(e) => {
+            drop(out);
+            let _ = audit::record(AuditEvent::new(
+                Action::Spawn,
+                uuid,
+                None,
+                workload,
+                Outcome::Failure(e.to_string()),
+            ))
+            .await;
+            BREAKER.failure();
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+    };
+    BREAKER.success();
+
+    if let Some(mut stdin) = exec.stdin.take() {
+        tokio::spawn(async move {
+            if let Err(e) = stdin.write_all(&toml).await {
+                tracing::error!("failed to write config of job {} to stdin: {}", uuid, e);
+            }
+        });
+    }
+
+    out.insert(
+        uuid,
+        Arc::new(Job {
+            created: Instant::now(),
+            workload: workload.clone(),
+            stdout: Mutex::new(exec.stdout.take()),
+            stderr: Mutex::new(exec.stderr.take()),
+            state: Mutex::new(State {
+                id: uuid,
+                exec,
+                wasm: Some(wasm),
+                toml: conf_file,
+                paused: false,
+                killed: false,
+            }),
+        }),
+    );
+    drop(out);
+    undo.disarm();
+
+    // The job is registered, so the rest runs in its own task: the handler is dropped
+    // if the client disconnects, which must not leave the job without its reaper.
+    let started = tokio::spawn(async move {
+        // A job that cannot be audited must not keep running.
+        if audit::record(AuditEvent::new(
+            Action::Spawn,
+            uuid,
+            None,
+            workload,
+            Outcome::Success,
+        ))
         .await
-        .insert(uuid, Arc::new(Mutex::new(State { exec, wasm, toml })));
+        .is_err()
+        {
+            kill(uuid).await;
+            return false;
+        }
+        events::emit(uuid, events::Kind::Spawned);
 
-    tokio::spawn(async move {
-        sleep(VIEW_TIMEOUT).await;
-        OUT.write().await.remove(&uuid);
+        tokio::spawn(async move {
+            sleep(VIEW_TIMEOUT).await;
+            kill(uuid).await;
+        });
+        tokio::spawn(wait(uuid));
+        true
     });
+    if !started.await.unwrap_or(false) {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+    }
 
     Ok((StatusCode::SEE_OTHER, [("Location", format!("/{}", uuid))]))
 }
 
+/// Removes the job from the registry and tears it down.
+/// Returns `None` if no such job is registered.
+async fn kill(uuid: Uuid) -> Option<CleanupReport> {
+    // The write lock makes removal atomic: exactly one caller gets to tear the job down.
+    let job = OUT.write().await.remove(&uuid)?;
+
+    // Waiting for the lock counts against the deadline, as the job may be busy.
+    // If it cannot be taken in time, teardown completes in the background once
+    // whoever holds the lock releases it.
+    let deadline = tokio::time::Instant::now() + KILL_TIMEOUT;
+    let report = match timeout_at(deadline, job.state.lock()).await {
+        Ok(mut state) => state.kill(deadline).await,
+        Err(..) => {
+            let job = job.clone();
+            tokio::spawn(async move {
+                let mut state = job.state.lock().await;
+                let report = state.kill(tokio::time::Instant::now() + KILL_TIMEOUT).await;
+                for (step, e) in &report.failures {
+                    tracing::error!("failed to clean up {} of job {}: {}", step, uuid, e);
+                }
+            });
+
+            let mut report = CleanupReport::default();
+            report.expired();
+            report
+        }
+    };
+    for (step, e) in &report.failures {
+        tracing::error!("failed to clean up {} of job {}: {}", step, uuid, e);
+    }
+
+    let _ = audit::record(AuditEvent::new(
+        Action::Kill,
+        uuid,
+        None,
+        job.workload.clone(),
+        report.outcome(),
+    ))
+    .await;
+    events::emit(uuid, events::Kind::Killed);
+
+    post_kill(uuid).await;
+    Some(report)
+}
+
+/// Emits [events::Kind::Exited] once the engine of the job exits on its own.
+///
+/// The engine is polled rather than awaited, as awaiting it would hold the job lock
+/// until it exits. Polling stops once the job is killed.
+async fn wait(uuid: Uuid) {
+    loop {
+        sleep(EXIT_POLL).await;
+        let job = match OUT.read().await.get(&uuid) {
+            Some(job) => job.clone(),
+            None => return,
+        };
+        let exited = matches!(job.state.lock().await.exec.try_wait(), Ok(Some(..)));
+        if exited {
+            events::emit(uuid, events::Kind::Exited);
+            return;
+        }
+    }
+}
+
+/// Runs the post-kill hook, undoing the pre-spawn hook of a job that was killed
+/// or that failed to start.
+async fn post_kill(uuid: Uuid) {
+    if let Some(hook) = POST_KILL.as_ref() {
+        if let Err(e) = hook.run(uuid, HOOK_TIMEOUT).await {
+            tracing::error!("post-kill hook failed for job {}: {}", uuid, e);
+        }
+    }
+}
+
+/// Runs the post-kill hook of a job when dropped, unless disarmed.
+struct Undo(Option<Uuid>);
+
+impl Undo {
+    /// Hands the job over to [kill], which runs the post-kill hook itself.
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for Undo {
+    fn drop(&mut self) {
+        if let Some(uuid) = self.0.take() {
+            tokio::spawn(post_kill(uuid));
+        }
+    }
+}
+
+/// A rejected spawn request, optionally explaining why.
+struct Rejection(StatusCode, Option<String>);
+
+impl From<StatusCode> for Rejection {
+    fn from(status: StatusCode) -> Self {
+        Self(status, None)
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Rejection(status, None) => status.into_response(),
+            Rejection(status, Some(message)) => (status, message).into_response(),
+        }
+    }
+}
+
+/// Lists all jobs carrying every label given in the query.
+async fn jobs_get(Query(selector): Query<HashMap<String, String>>) -> Json<Vec<JobDescription>> {
+    // Labels live outside of the job locks, so only matching jobs are locked to be described.
+    let jobs: Vec<_> = OUT
+        .read()
+        .await
+        .values()
+        .filter(|job| {
+            selector
+                .iter()
+                .all(|(k, v)| job.workload.labels.get(k) == Some(v))
+        })
+        .cloned()
+        .collect();
+
+    let mut descriptions = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        descriptions.push(job.describe().await);
+    }
+    Json(descriptions)
+}
+
+/// Describes a single job.
+async fn jobs_uuid_get(Path(uuid): Path<String>) -> Result<impl IntoResponse, StatusCode> {
+    let uuid: Uuid = uuid.parse().map_err(|_| StatusCode::NOT_FOUND)?;
+    let exec = OUT
+        .read()
+        .await
+        .get(&uuid)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+
+    let description = exec.describe().await;
+    Ok(Json(description))
+}
+
+async fn engines_get() -> Json<&'static HashMap<String, Option<Version>>> {
+    Json(VERSIONS.get().unwrap())
+}
+
+/// Which jobs [reclaim_post] kills first.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ReclaimPolicy {
+    #[default]
+    Oldest,
+    Newest,
+}
+
+#[derive(Deserialize)]
+struct ReclaimQuery {
+    count: usize,
+    #[serde(default)]
+    policy: ReclaimPolicy,
+}
+
+/// Kills up to `count` jobs to free capacity, returning the ids of the killed jobs.
+async fn reclaim_post(Query(query): Query<ReclaimQuery>) -> Json<Vec<Uuid>> {
+    let mut ages: Vec<_> = OUT
+        .read()
+        .await
+        .iter()
+        .map(|(id, job)| (job.created, *id))
+        .collect();
+    ages.sort_unstable();
+    if let ReclaimPolicy::Newest = query.policy {
+        ages.reverse();
+    }
+
+    let mut killed = Vec::new();
+    for (_, id) in ages.into_iter().take(query.count) {
+        // As in [uuid_delete], a client disconnecting must not interrupt a teardown.
+        if let Ok(Some(..)) = tokio::spawn(kill(id)).await {
+            killed.push(id);
+        }
+    }
+    Json(killed)
+}
+
+/// The remaining headroom for new jobs.
+#[derive(Serialize)]
+struct Capacity {
+    active_jobs: usize,
+    max_jobs: usize,
+}
+
+async fn capacity_get() -> Json<Capacity> {
+    Json(Capacity {
+        active_jobs: OUT.read().await.len(),
+        max_jobs: *JOBS_LIMIT,
+    })
+}
+
+async fn events_get() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = BroadcastStream::new(events::subscribe()).filter_map(|event| match event {
+        Ok(event) => Some(Ok(Event::default()
+            .json_data(event)
+            .expect("JobEvent serializes"))),
+        Err(BroadcastStreamRecvError::Lagged(n)) => {
+            tracing::warn!("events subscriber lagged, dropped {} events", n);
+            None
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Reads a short UTF-8 text field of at most `max` bytes.
+async fn read_text(mut field: Field<'_>, max: usize) -> Result<String, StatusCode> {
+    if field.content_type().is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut out = Vec::new();
+    while let Some(chunk) = field.chunk().await.map_err(|_| StatusCode::BAD_REQUEST)? {
+        if out.len() + chunk.len() > max {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        out.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(out).map_err(|_| StatusCode::BAD_REQUEST)
+}
+
 async fn uuid_get(Path(uuid): Path<String>) -> Result<impl IntoResponse, StatusCode> {
     let uuid: Uuid = uuid.parse().map_err(|_| StatusCode::NOT_FOUND)?;
     OUT.read().await.get(&uuid).ok_or(StatusCode::NOT_FOUND)?;
     Ok(Html(include_str!("uuid_get.html")))
 }
 
+async fn uuid_delete(Path(uuid): Path<String>) -> Result<impl IntoResponse, StatusCode> {
+    let uuid: Uuid = uuid.parse().map_err(|_| StatusCode::NOT_FOUND)?;
+    // Teardown runs in its own task, so that a client disconnecting cannot
+    // interrupt it once the job has been removed from the registry.
+    let report = tokio::spawn(kill(uuid))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !report.failures.is_empty() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn uuid_pause_post(Path(uuid): Path<String>) -> Result<impl IntoResponse, StatusCode> {
+    let uuid: Uuid = uuid.parse().map_err(|_| StatusCode::NOT_FOUND)?;
+    let exec = OUT
+        .read()
+        .await
+        .get(&uuid)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+
+    exec.state
+        .lock()
+        .await
+        .pause()
+        .map_err(|_| StatusCode::CONFLICT)?;
+    events::emit(uuid, events::Kind::Paused);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn uuid_resume_post(Path(uuid): Path<String>) -> Result<impl IntoResponse, StatusCode> {
+    let uuid: Uuid = uuid.parse().map_err(|_| StatusCode::NOT_FOUND)?;
+    let exec = OUT
+        .read()
+        .await
+        .get(&uuid)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+
+    exec.state
+        .lock()
+        .await
+        .resume()
+        .map_err(|_| StatusCode::CONFLICT)?;
+    events::emit(uuid, events::Kind::Resumed);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 async fn uuid_out_post(Path(uuid): Path<String>) -> Result<impl IntoResponse, StatusCode> {
     let mut buf = [0; 4096];
 
@@ -174,18 +1039,16 @@ async fn uuid_out_post(Path(uuid): Path<String>) -> Result<impl IntoResponse, St
         .clone();
 
     let future = async {
-        exec.lock()
-            .await
-            .exec
-            .stdout
-            .as_mut()
-            .unwrap()
+        let mut stdout = exec.stdout.lock().await;
+        let stdout = stdout.as_mut().ok_or(StatusCode::NOT_FOUND)?;
+        stdout
             .read(&mut buf)
             .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
     };
 
     match timeout(READ_TIMEOUT, future).await {
-        Ok(Err(..)) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Ok(Err(e)) => Err(e),
         Ok(Ok(size)) => Ok(buf[..size].to_vec()),
         Err(..) => Ok(Vec::new()),
     }
@@ -203,18 +1066,16 @@ async fn uuid_err_post(Path(uuid): Path<String>) -> Result<impl IntoResponse, St
         .clone();
 
     let future = async {
-        exec.lock()
-            .await
-            .exec
-            .stderr
-            .as_mut()
-            .unwrap()
+        let mut stderr = exec.stderr.lock().await;
+        let stderr = stderr.as_mut().ok_or(StatusCode::NOT_FOUND)?;
+        stderr
             .read(&mut buf)
             .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
     };
 
     match timeout(READ_TIMEOUT, future).await {
-        Ok(Err(..)) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Ok(Err(e)) => Err(e),
         Ok(Ok(size)) => Ok(buf[..size].to_vec()),
         Err(..) => Ok(Vec::new()),
     }
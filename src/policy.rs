@@ -0,0 +1,135 @@
+use std::fmt;
+use std::str::FromStr;
+
+use toml::Value;
+
+/// Top-level keys an uploaded `Enarx.toml` may declare.
+const KEYS: &[&str] = &["steward", "args", "env", "files"];
+
+/// Restrictions on what an uploaded `Enarx.toml` may declare.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    /// The `kind`s of `[[files]]` entries workloads may request.
+    kinds: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PolicyError {
+    Parse(String),
+    UnknownKey(String),
+    DisallowedKind(String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Parse(e) => write!(f, "invalid Enarx.toml: {}", e),
+            PolicyError::UnknownKey(key) => write!(f, "Enarx.toml key `{}` is not allowed", key),
+            PolicyError::DisallowedKind(kind) => {
+                write!(f, "Enarx.toml file kind `{}` is not allowed", kind)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+impl Policy {
+    /// Checks the config against the policy, returning the first offending key.
+    pub fn validate(&self, toml: &[u8]) -> Result<(), PolicyError> {
+        let toml = std::str::from_utf8(toml).map_err(|e| PolicyError::Parse(e.to_string()))?;
+        let config: toml::value::Table =
+            toml::from_str(toml).map_err(|e| PolicyError::Parse(e.to_string()))?;
+
+        if let Some(key) = config.keys().find(|key| !KEYS.contains(&key.as_str())) {
+            return Err(PolicyError::UnknownKey(key.clone()));
+        }
+
+        let files = match config.get("files") {
+            None => return Ok(()),
+            Some(Value::Array(files)) => files,
+            Some(..) => return Err(PolicyError::Parse("`files` must be an array".into())),
+        };
+        for file in files {
+            let kind = file
+                .get("kind")
+                .and_then(Value::as_str)
+                .ok_or_else(|| PolicyError::Parse("`files` entry without a `kind`".into()))?;
+            if !self.kinds.iter().any(|k| k == kind) {
+                return Err(PolicyError::DisallowedKind(kind.into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Policy {
+    type Err = std::convert::Infallible;
+
+    /// Parses a comma-separated list of allowed file kinds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let kinds = s
+            .split(',')
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(String::from)
+            .collect();
+        Ok(Self { kinds })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Policy, PolicyError};
+
+    fn policy(kinds: &str) -> Policy {
+        kinds.parse().unwrap()
+    }
+
+    #[test]
+    fn validate_allows_known_keys() {
+        let toml = br#"
+            args = ["--verbose"]
+            env = { RUST_LOG = "info" }
+
+            [[files]]
+            kind = "stdin"
+            name = "stdin"
+        "#;
+        assert!(policy("stdin, stdout").validate(toml).is_ok());
+        assert!(policy("").validate(b"args = []").is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_key() {
+        let err = policy("stdin").validate(b"network = true").unwrap_err();
+        assert!(matches!(err, PolicyError::UnknownKey(key) if key == "network"));
+    }
+
+    #[test]
+    fn validate_rejects_disallowed_kind() {
+        let toml = br#"
+            [[files]]
+            kind = "listen"
+            name = "http"
+        "#;
+        let err = policy("stdin,stdout").validate(toml).unwrap_err();
+        assert!(matches!(err, PolicyError::DisallowedKind(kind) if kind == "listen"));
+    }
+
+    #[test]
+    fn validate_rejects_malformed_config() {
+        assert!(matches!(
+            policy("stdin").validate(b"files = ["),
+            Err(PolicyError::Parse(..))
+        ));
+        assert!(matches!(
+            policy("stdin").validate(b"files = 1"),
+            Err(PolicyError::Parse(..))
+        ));
+        assert!(matches!(
+            policy("stdin").validate(b"[[files]]\nname = \"x\""),
+            Err(PolicyError::Parse(..))
+        ));
+    }
+}
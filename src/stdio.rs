@@ -0,0 +1,67 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::str::FromStr;
+
+/// How the engine's stdout and stderr are handled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum StdioMode {
+    /// Piped to benefice and streamed to the client.
+    #[default]
+    Piped,
+    /// Inherited from benefice itself, useful for debugging.
+    Inherit,
+    /// Discarded.
+    Null,
+    /// Appended to the file at the given path.
+    File(PathBuf),
+}
+
+impl StdioMode {
+    /// Constructs a [Stdio] for a single stream of the engine process.
+    pub fn stdio(&self) -> io::Result<Stdio> {
+        Ok(match self {
+            StdioMode::Piped => Stdio::piped(),
+            StdioMode::Inherit => Stdio::inherit(),
+            StdioMode::Null => Stdio::null(),
+            StdioMode::File(path) => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .into(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseStdioModeError(String);
+
+impl fmt::Display for ParseStdioModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid stdio mode `{}`, expected `piped`, `inherit`, `null` or `file:<path>`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseStdioModeError {}
+
+impl FromStr for StdioMode {
+    type Err = ParseStdioModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "piped" => Ok(StdioMode::Piped),
+            "inherit" => Ok(StdioMode::Inherit),
+            "null" => Ok(StdioMode::Null),
+            _ => match s.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(StdioMode::File(path.into())),
+                _ => Err(ParseStdioModeError(s.into())),
+            },
+        }
+    }
+}